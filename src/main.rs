@@ -1,11 +1,15 @@
-use anyhow::{bail, Result};
-use clap::Parser;
-use good_lp::solvers::lp_solvers::{GurobiSolver, LpSolver, Model};
-use good_lp::{variable, Expression, ProblemVariables, SolverModel, Variable};
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use good_lp::solvers::lp_solvers::{GurobiSolver, LpSolver};
+use good_lp::{
+    coin_cbc, highs, scip, variable, Expression, ProblemVariables, ResolutionError, Solution,
+    Solver, SolverModel, Variable,
+};
 use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::path::{Path, PathBuf};
 
-/// A simple utility to convert standard-format Sudoku puzzles
+/// A simple utility to convert standard-format Sudoku (and Hidato) puzzles
 /// to LP (for use with a binary-integer, linear-programming solver).
 #[derive(Debug, Parser)]
 #[clap(name = "sudoku2lp", version = "0.1.0", author = "Andrew Henshaw")]
@@ -14,84 +18,897 @@ pub struct AppArgs {
     out_file: Option<PathBuf>,
     #[arg(short, long, help = "Solve puzzle (if solver available)")]
     solve: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PuzzleType::Sudoku,
+        help = "Puzzle type to encode"
+    )]
+    puzzle: PuzzleType,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SolverBackend::Cbc,
+        help = "MILP solver backend to use with --solve"
+    )]
+    solver: SolverBackend,
+    #[arg(
+        long,
+        env = "GUROBI_CL",
+        help = "Path to the gurobi_cl binary (only used with --solver gurobi)"
+    )]
+    gurobi_path: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Lp,
+        help = "Format to write the constraint system in"
+    )]
+    format: OutputFormat,
+    #[arg(long, help = "Add X-Sudoku diagonal constraints")]
+    diagonal: bool,
+    #[arg(
+        long,
+        help = "Add Windoku constraints (the 4 shaded interior 3x3 boxes); 9x9 only"
+    )]
+    windoku: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Path to a Killer Sudoku cage file adding cage-sum constraints"
+    )]
+    killer: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "After solving, add a no-good cut and re-solve to check the puzzle has a unique solution"
+    )]
+    check_unique: bool,
+    #[arg(
+        long,
+        conflicts_with = "decompress",
+        help = "Compress a fully solved grid (in_file, or the grid found with --solve) to a compact printable token"
+    )]
+    compress: bool,
+    #[arg(
+        long,
+        conflicts_with = "compress",
+        help = "Decompress a token (in_file) produced by --compress back into a solved grid"
+    )]
+    decompress: bool,
+    #[arg(
+        long,
+        default_value_t = 9,
+        help = "Board size, used only by --decompress (the token doesn't store it)"
+    )]
+    size: usize,
 }
 
+/// The puzzle types this crate can encode as an LP/MPS constraint system.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PuzzleType {
+    /// Classic Sudoku, plus its X-Sudoku/Windoku/Killer variants.
+    Sudoku,
+    /// Hidato: link consecutive numbers through adjacent cells on a
+    /// (possibly holed, non-rectangular) grid.
+    Hidato,
+}
+
+/// The available LP/MPS solver backends, routed through good_lp.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SolverBackend {
+    /// The free COIN-OR CBC solver, bundled with good_lp.
+    Cbc,
+    /// The free HiGHS solver.
+    Highs,
+    /// The free SCIP solver (must be installed separately).
+    Scip,
+    /// Gurobi, invoked as an external command-line solver.
+    Gurobi,
+}
+
+/// The file formats the constraint system can be emitted as.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// CPLEX-style LP text.
+    Lp,
+    /// Fixed MPS.
+    Mps,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Lp => "lp",
+            OutputFormat::Mps => "mps",
+        }
+    }
+}
+
+const DEFAULT_GUROBI_PATH: &str = "/opt/gurobi1001/linux64/bin/gurobi_cl";
+
 fn main() {
+    if let Err(e) = run_cli() {
+        eprintln!("{e}");
+    }
+}
+
+fn run_cli() -> Result<()> {
     let args = AppArgs::parse();
 
-    // if out_file not provided, use in_file base + ".lp"
-    let mut out_file = args.out_file.unwrap_or(args.in_file.clone());
-    out_file.set_extension("lp");
+    // if out_file not provided, use in_file base + the extension for --format
+    let mut out_file = args.out_file.clone().unwrap_or(args.in_file.clone());
+    if args.out_file.is_none() {
+        out_file.set_extension(args.format.extension());
+    }
+
+    if let PuzzleType::Hidato = args.puzzle {
+        if args.compress || args.decompress {
+            bail!("--compress/--decompress are only supported for --puzzle sudoku");
+        }
+        if args.diagonal || args.windoku || args.killer.is_some() {
+            bail!("--diagonal/--windoku/--killer are only supported for --puzzle sudoku");
+        }
+        return run_cli_hidato(&args, &out_file);
+    }
+
+    if args.decompress {
+        if args.size == 0 {
+            bail!("--size must be greater than 0");
+        }
+        let code = std::fs::read_to_string(&args.in_file)?;
+        let (box_rows, box_cols) = box_dims(args.size);
+        println!(
+            "{}",
+            decompress(code.trim_end_matches(&['\r', '\n'][..]), args.size, box_rows, box_cols)?
+        );
+        return Ok(());
+    }
+
+    let puzzle = load(&args.in_file)?;
+    if let Some(solution) = &puzzle.solution {
+        println!("stored solution: {solution}");
+    }
+    let body = puzzle.body;
+
+    if args.compress && !args.solve {
+        let size = (body.len() as f64).sqrt().round() as usize;
+        if size == 0 || size * size != body.len() || body.contains('0') {
+            bail!("--compress requires a fully solved grid (no blanks) as in_file");
+        }
+        let (box_rows, box_cols) = box_dims(size);
+        println!("{}", compress(&body, size, box_rows, box_cols)?);
+        return Ok(());
+    }
+
+    let cages = match &args.killer {
+        Some(file) => {
+            let size = (body.len() as f64).sqrt().round() as usize;
+            load_cages(file, size)?
+        }
+        None => Vec::new(),
+    };
+    let variants = Variants {
+        diagonal: args.diagonal,
+        windoku: args.windoku,
+        cages,
+        exclude: Vec::new(),
+    };
+
+    match args.solver {
+        SolverBackend::Cbc => run(
+            |v| generate(&body, coin_cbc, v),
+            &variants,
+            &args,
+            &out_file,
+        ),
+        SolverBackend::Highs => run(
+            |v| generate(&body, highs, v),
+            &variants,
+            &args,
+            &out_file,
+        ),
+        SolverBackend::Scip => run(
+            |v| generate(&body, scip, v),
+            &variants,
+            &args,
+            &out_file,
+        ),
+        SolverBackend::Gurobi => {
+            let gurobi_path = args
+                .gurobi_path
+                .clone()
+                .unwrap_or_else(|| DEFAULT_GUROBI_PATH.to_string());
+            run(
+                |v| {
+                    let gurobi = GurobiSolver::new().command_name(gurobi_path.clone());
+                    generate(&body, LpSolver(gurobi), v)
+                },
+                &variants,
+                &args,
+                &out_file,
+            )
+        }
+    }
+}
+
+/// Load a Hidato puzzle and dispatch it to the selected solver backend.
+fn run_cli_hidato(args: &AppArgs, out_file: &Path) -> Result<()> {
+    let puzzle = load_hidato(&args.in_file)?;
+
+    match args.solver {
+        SolverBackend::Cbc => run_hidato(
+            |exclude| generate_hidato(&puzzle, coin_cbc, exclude),
+            args,
+            out_file,
+        ),
+        SolverBackend::Highs => run_hidato(
+            |exclude| generate_hidato(&puzzle, highs, exclude),
+            args,
+            out_file,
+        ),
+        SolverBackend::Scip => run_hidato(
+            |exclude| generate_hidato(&puzzle, scip, exclude),
+            args,
+            out_file,
+        ),
+        SolverBackend::Gurobi => {
+            let gurobi_path = args
+                .gurobi_path
+                .clone()
+                .unwrap_or_else(|| DEFAULT_GUROBI_PATH.to_string());
+            run_hidato(
+                |exclude| {
+                    let gurobi = GurobiSolver::new().command_name(gurobi_path.clone());
+                    generate_hidato(&puzzle, LpSolver(gurobi), exclude)
+                },
+                args,
+                out_file,
+            )
+        }
+    }
+}
+
+/// Emit the Hidato constraint system and, if requested, hand the model to
+/// the solver, rebuilding with `build` again (with a no-good cut) if
+/// `--check-unique` is set.
+fn run_hidato<M, F>(build: F, args: &AppArgs, out_file: &Path) -> Result<()>
+where
+    M: SolverModel<Error = ResolutionError>,
+    M::Solution: Solution,
+    F: Fn(&[Vec<(usize, usize)>]) -> Result<(M, LpProblem, HidatoBoard)>,
+{
+    let (model, lp, board) = build(&[])?;
+    emit(&lp, out_file, args.format)?;
+    if !args.solve {
+        return Ok(());
+    }
+
+    let Some(grid) = solve_hidato(model, &board)? else {
+        return Ok(());
+    };
+    print_hidato_grid(&grid);
+
+    if args.check_unique {
+        let exclude = vec![board.assignment(&grid)];
+        let (cut_model, _, _) = build(&exclude)?;
+        match cut_model.solve() {
+            Ok(_) => println!("not unique: a second solution exists"),
+            Err(ResolutionError::Infeasible) => println!("unique"),
+            Err(e) => bail!("could not determine uniqueness: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Solve a Hidato model and, on success, read back the solved grid.
+fn solve_hidato<M: SolverModel>(
+    model: M,
+    board: &HidatoBoard,
+) -> Result<Option<Vec<Vec<Option<usize>>>>>
+where
+    M::Solution: Solution,
+{
+    match model.solve() {
+        Ok(solution) => Ok(Some(board.read_grid(&solution))),
+        Err(e) => {
+            eprintln!("{e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Print a solved Hidato grid, one row per line, right-aligned to the
+/// widest value, with holes shown as `.`.
+fn print_hidato_grid(grid: &[Vec<Option<usize>>]) {
+    let width = grid
+        .iter()
+        .flatten()
+        .filter_map(|v| *v)
+        .map(|v| v.to_string().len())
+        .max()
+        .unwrap_or(1);
+    for row in grid {
+        let line: Vec<String> = row
+            .iter()
+            .map(|cell| match cell {
+                Some(v) => format!("{v:width$}"),
+                None => format!("{:>width$}", "."),
+            })
+            .collect();
+        println!("{}", line.join(" "));
+    }
+}
+
+/// Emit the constraint system and, if requested, hand the model to the solver,
+/// rebuilding with `build` again (with a no-good cut) if `--check-unique` is set.
+fn run<M, F>(build: F, variants: &Variants, args: &AppArgs, out_file: &Path) -> Result<()>
+where
+    M: SolverModel<Error = ResolutionError>,
+    M::Solution: Solution,
+    F: Fn(&Variants) -> Result<(M, LpProblem, Board)>,
+{
+    let (model, lp, board) = build(variants)?;
+    emit(&lp, out_file, args.format)?;
+    if !args.solve {
+        return Ok(());
+    }
+
+    let Some(grid) = solve(model, &board)? else {
+        return Ok(());
+    };
+    print_grid(&grid);
+
+    if args.compress {
+        let flat: String = grid.iter().flatten().collect();
+        println!(
+            "{}",
+            compress(&flat, board.size, board.box_rows, board.box_cols)?
+        );
+    }
+
+    if args.check_unique {
+        let mut excluded = variants.clone();
+        excluded.exclude.push(board.assignment(&grid));
+        let (cut_model, _, _) = build(&excluded)?;
+        match cut_model.solve() {
+            Ok(_) => println!("not unique: a second solution exists"),
+            Err(ResolutionError::Infeasible) => println!("unique"),
+            Err(e) => bail!("could not determine uniqueness: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Solve a model and, on success, read back the solved grid.
+fn solve<M: SolverModel>(model: M, board: &Board) -> Result<Option<Vec<Vec<char>>>>
+where
+    M::Solution: Solution,
+{
+    match model.solve() {
+        Ok(solution) => Ok(Some(board.read_grid(&solution))),
+        Err(e) => {
+            eprintln!("{e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Print a solved grid, one row per line.
+fn print_grid(grid: &[Vec<char>]) {
+    for row in grid {
+        println!("{}", row.iter().collect::<String>());
+    }
+}
+
+/// A loaded puzzle: the normalized body fed to `generate`, plus a stored
+/// solution when the source file provided one (ksudoku files may).
+struct Puzzle {
+    body: String,
+    solution: Option<String>,
+}
+
+/// Load and normalize a puzzle, either a flat token string or a ksudoku
+/// `.ksudoku` XML save file. Tokens are `0` for a blank cell, `1`-`9` for
+/// values 1-9, and `A`-`Z` for values 10 and up, so 16x16 boards use `1`-`9`
+/// then `A`-`G`, 25x25 boards use `1`-`9` then `A`-`P`, and so on.
+fn load(file: &Path) -> Result<Puzzle> {
+    let text = std::fs::read_to_string(file)?;
+    if text.trim_start().starts_with("<?xml") || text.contains("<KSudoku") {
+        load_ksudoku(&text)
+    } else {
+        Ok(Puzzle {
+            body: normalize_tokens(&text),
+            solution: None,
+        })
+    }
+}
 
-    if let Ok(puzzle) = load(&args.in_file) {
-        if let Ok(model) = generate(&puzzle) {
-            if args.solve {
-                match model.solve() {
-                    Ok(_solution) => println!("solved"),
-                    Err(e) => eprintln!("{e}"),
+/// Normalize free-form puzzle text down to its `0`-`9`/`A`-`Z` tokens.
+fn normalize_tokens(text: &str) -> String {
+    let text = text.to_uppercase().replace('.', "0");
+    text.chars()
+        .filter(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+        .collect()
+}
+
+/// Parse a ksudoku `.ksudoku` XML save file, extracting the puzzle body and,
+/// if present, the stored solution.
+fn load_ksudoku(text: &str) -> Result<Puzzle> {
+    let puzzle_text = extract_tag(text, "Puzzle").context("ksudoku file missing a <Puzzle> tag")?;
+    let body = normalize_tokens(puzzle_text);
+    let solution = extract_tag(text, "Solution").map(normalize_tokens);
+
+    if let Some(order) = extract_attr(text, "Game", "order").and_then(|o| o.parse::<usize>().ok())
+    {
+        let size = order * order;
+        if body.len() != size * size {
+            bail!(
+                "ksudoku order={order} implies a {size}x{size} board but the puzzle has {} cells",
+                body.len()
+            );
+        }
+    }
+
+    Ok(Puzzle { body, solution })
+}
+
+/// The trimmed text content of the first `<tag>...</tag>` element found.
+fn extract_tag<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let open_start = text.find(&format!("<{tag}"))?;
+    let open_end = open_start + text[open_start..].find('>')? + 1;
+    let close_start = open_end + text[open_end..].find(&format!("</{tag}>"))?;
+    Some(text[open_end..close_start].trim())
+}
+
+/// The value of `attr` on the first `<tag ...>` element found.
+fn extract_attr(text: &str, tag: &str, attr: &str) -> Option<String> {
+    let open_start = text.find(&format!("<{tag}"))?;
+    let open_end = open_start + text[open_start..].find('>')?;
+    let header = &text[open_start..open_end];
+    let value_start = header.find(&format!("{attr}=\""))? + attr.len() + 2;
+    let value_end = value_start + header[value_start..].find('"')?;
+    Some(header[value_start..value_end].to_string())
+}
+
+/// The numeric value of a puzzle token: `0` for blank, `1`-`9` literally,
+/// and `A`-`Z` for 10 and up.
+fn token_value(c: char) -> usize {
+    if let Some(d) = c.to_digit(10) {
+        d as usize
+    } else {
+        10 + (c as u8 - b'A') as usize
+    }
+}
+
+/// A Killer Sudoku cage: a set of cells whose values must sum to `target`.
+#[derive(Debug, Clone)]
+struct Cage {
+    cells: Vec<(usize, usize)>,
+    target: usize,
+}
+
+/// Load Killer Sudoku cages from a companion file. Each line describes one
+/// cage as `target: r1,c1 r2,c2 ...` using 1-indexed row,col pairs, each of
+/// which must fall within the `size`x`size` board.
+fn load_cages(file: &Path, size: usize) -> Result<Vec<Cage>> {
+    let text = std::fs::read_to_string(file)?;
+    let mut cages = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (target, cells) = line
+            .split_once(':')
+            .context("cage line must be `target: r1,c1 r2,c2 ...`")?;
+        let target: usize = target.trim().parse().context("invalid cage target sum")?;
+        let cells = cells
+            .split_whitespace()
+            .map(|pair| {
+                let (row, col) = pair
+                    .split_once(',')
+                    .context("invalid cage cell, expected row,col")?;
+                let (row, col): (usize, usize) = (row.trim().parse()?, col.trim().parse()?);
+                if row < 1 || row > size || col < 1 || col > size {
+                    bail!("invalid cage cell {row},{col}: outside the {size}x{size} board");
                 }
+                Ok((row, col))
+            })
+            .collect::<Result<Vec<(usize, usize)>>>()?;
+        cages.push(Cage { cells, target });
+    }
+    Ok(cages)
+}
+
+/// Which optional Sudoku variant rules to add on top of classic Sudoku.
+#[derive(Debug, Default, Clone)]
+struct Variants {
+    /// X-Sudoku: both main diagonals must also contain each value once.
+    diagonal: bool,
+    /// Windoku: the 4 shaded interior 3x3 boxes must also contain each value once.
+    windoku: bool,
+    /// Killer Sudoku cages: each cage's cells must sum to its target.
+    cages: Vec<Cage>,
+    /// Full-grid assignments to forbid via a no-good cut (used by `--check-unique`).
+    exclude: Vec<Vec<(usize, usize, usize)>>,
+}
+
+/// The numeric-value -> token mapping used when printing a solved grid: the
+/// inverse of `token_value`.
+fn value_char(v: usize) -> char {
+    if v <= 9 {
+        char::from_digit(v as u32, 10).unwrap()
+    } else {
+        (b'A' + (v - 10) as u8) as char
+    }
+}
+
+/// The `x[row,col,num]` variables for a puzzle of the given `size`, kept
+/// around after `generate` so a found solution can be read back into a grid.
+struct Board {
+    size: usize,
+    box_rows: usize,
+    box_cols: usize,
+    x: HashMap<(usize, usize, usize), Variable>,
+}
+
+impl Board {
+    /// Read back the solved grid as `size` rows of `size` characters.
+    fn read_grid<Sol: Solution>(&self, solution: &Sol) -> Vec<Vec<char>> {
+        (1..=self.size)
+            .map(|row| {
+                (1..=self.size)
+                    .map(|col| {
+                        let num = (1..=self.size)
+                            .find(|&num| solution.value(self.x[&(row, col, num)]) > 0.5)
+                            .expect("every cell is assigned exactly one value");
+                        value_char(num)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The `(row, col, num)` assignment implied by a solved grid, suitable
+    /// for forbidding via a no-good cut.
+    fn assignment(&self, grid: &[Vec<char>]) -> Vec<(usize, usize, usize)> {
+        let mut assignment = Vec::with_capacity(self.size * self.size);
+        for (r, row) in grid.iter().enumerate() {
+            for (c, &ch) in row.iter().enumerate() {
+                assignment.push((r + 1, c + 1, token_value(ch)));
             }
         }
+        assignment
     }
 }
 
-/// Load and normalize the puzzle
-fn load(file: &Path) -> Result<String> {
-    let puzzle = std::fs::read_to_string(file)?;
-    let mut puzzle = puzzle.replace(".", "0");
-    puzzle.retain(|c| c.is_digit(10));
-    Ok(puzzle)
+/// The `(box_rows, box_cols)` pair for a board of the given `size`: the
+/// factor pair of `size` closest to square, preferring `box_rows <= box_cols`,
+/// which falls back naturally to the 2x3 style for non-square sizes like 6.
+fn box_dims(size: usize) -> (usize, usize) {
+    let mut box_rows = (size as f64).sqrt() as usize;
+    while box_rows > 1 && size % box_rows != 0 {
+        box_rows -= 1;
+    }
+    (box_rows, size / box_rows)
 }
 
-/// Create LP from normalized puzzle.  Assumptions
-/// are that the puzzle is either 4x4, 6x6, or 9x9.
-fn generate(puzzle: &str) -> Result<Model<GurobiSolver>> {
-    let (size, box_rows, box_cols) = match puzzle.len() {
-        81 => (9, 3, 3), // standard sudoku
-        36 => (6, 2, 3), // 6x6, this is the only one where num box rows != box cols
-        16 => (4, 2, 2), // 4x4
-        _ => bail!("Expected 9x9, 6x6, or 4x4 puzzle"),
+/// The index (0-based) of the box containing `(row, col)` (both 1-indexed).
+fn box_index(row: usize, col: usize, size: usize, box_rows: usize, box_cols: usize) -> usize {
+    let box_row = (row - 1) / box_rows;
+    let box_col = (col - 1) / box_cols;
+    box_row * (size / box_cols) + box_col
+}
+
+/// An arbitrary-precision non-negative integer, stored as little-endian
+/// base 2^32 limbs, just big enough to pack/unpack compressed boards.
+#[derive(Default, Clone)]
+struct BigUint {
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|&limb| limb == 0)
+    }
+
+    /// `self = self * mul + add`, for small `mul`/`add`.
+    fn mul_add(&mut self, mul: u64, add: u64) {
+        let mut carry = add;
+        for limb in self.limbs.iter_mut() {
+            let v = *limb * mul + carry;
+            *limb = v & 0xFFFF_FFFF;
+            carry = v >> 32;
+        }
+        while carry > 0 {
+            self.limbs.push(carry & 0xFFFF_FFFF);
+            carry >>= 32;
+        }
+    }
+
+    /// `self /= div`, returning the remainder, for small `div`.
+    fn div_mod(&mut self, div: u64) -> u64 {
+        let mut rem: u64 = 0;
+        for limb in self.limbs.iter_mut().rev() {
+            let cur = (rem << 32) | *limb;
+            *limb = cur / div;
+            rem = cur % div;
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        rem
+    }
+}
+
+/// The base-95 printable-ASCII alphabet used by `--compress`/`--decompress`
+/// (the full range of printable ASCII, ` ` through `~`).
+const PRINTABLE: std::ops::RangeInclusive<u8> = 32..=126;
+
+fn to_base95(mut num: BigUint) -> String {
+    if num.is_zero() {
+        return (*PRINTABLE.start() as char).to_string();
+    }
+    let mut chars = Vec::new();
+    while !num.is_zero() {
+        let digit = num.div_mod(95);
+        chars.push((PRINTABLE.start() + digit as u8) as char);
+    }
+    chars.iter().rev().collect()
+}
+
+fn from_base95(code: &str) -> BigUint {
+    let mut num = BigUint::default();
+    for c in code.chars() {
+        let digit = (c as u8).saturating_sub(*PRINTABLE.start()) as u64;
+        num.mul_add(95, digit);
+    }
+    num
+}
+
+/// Compress a fully solved `size`x`size` grid (as a flat token string) into a
+/// compact printable token. Walking the grid in row order, only cells whose
+/// value isn't already implied by their row/col/box peers are recorded, each
+/// as an index into its remaining-candidate list; these mixed-radix digits
+/// are then packed into one big integer and rendered in base-95.
+///
+/// Fails if `grid` isn't actually a valid solved board (a duplicate value in
+/// some row/col/box means that value won't be in the recomputed candidate
+/// set for its cell).
+fn compress(grid: &str, size: usize, box_rows: usize, box_cols: usize) -> Result<String> {
+    let values: Vec<usize> = grid.chars().map(token_value).collect();
+    let mut row_used = vec![0u32; size + 1];
+    let mut col_used = vec![0u32; size + 1];
+    let mut box_used = vec![0u32; size];
+    let mut digits = Vec::new();
+
+    for row in 1..=size {
+        for col in 1..=size {
+            let b = box_index(row, col, size, box_rows, box_cols);
+            let used = row_used[row] | col_used[col] | box_used[b];
+            let candidates: Vec<usize> = (1..=size).filter(|v| used & (1 << (v - 1)) == 0).collect();
+            let value = values[(row - 1) * size + (col - 1)];
+            let index = candidates
+                .iter()
+                .position(|&c| c == value)
+                .context("grid is not a valid solved board: a value repeats in its row, column, or box")?;
+            if candidates.len() > 1 {
+                digits.push((candidates.len() as u64, index as u64));
+            }
+            row_used[row] |= 1 << (value - 1);
+            col_used[col] |= 1 << (value - 1);
+            box_used[b] |= 1 << (value - 1);
+        }
+    }
+
+    let mut num = BigUint::default();
+    for &(radix, digit) in digits.iter().rev() {
+        num.mul_add(radix, digit);
+    }
+    Ok(to_base95(num))
+}
+
+/// The inverse of `compress`: replay the same row/col/box propagation,
+/// pulling each non-implied cell's value back out of the packed integer.
+fn decompress(code: &str, size: usize, box_rows: usize, box_cols: usize) -> Result<String> {
+    let mut num = from_base95(code);
+    let mut row_used = vec![0u32; size + 1];
+    let mut col_used = vec![0u32; size + 1];
+    let mut box_used = vec![0u32; size];
+    let mut grid = String::with_capacity(size * size);
+
+    for row in 1..=size {
+        for col in 1..=size {
+            let b = box_index(row, col, size, box_rows, box_cols);
+            let used = row_used[row] | col_used[col] | box_used[b];
+            let candidates: Vec<usize> = (1..=size).filter(|v| used & (1 << (v - 1)) == 0).collect();
+            if candidates.is_empty() {
+                bail!("compressed token does not decode to a valid {size}x{size} board");
+            }
+            let value = if candidates.len() == 1 {
+                candidates[0]
+            } else {
+                let index = num.div_mod(candidates.len() as u64) as usize;
+                candidates[index]
+            };
+            grid.push(value_char(value));
+            row_used[row] |= 1 << (value - 1);
+            col_used[col] |= 1 << (value - 1);
+            box_used[b] |= 1 << (value - 1);
+        }
+    }
+
+    Ok(grid)
+}
+
+/// A constraint comparison operator, kept independent of any particular
+/// solver crate so it can be serialized to either LP or MPS text.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Eq,
+    Geq,
+    Leq,
+}
+
+/// A single linear constraint: `sum(coeff * var) op rhs`.
+#[derive(Debug)]
+struct LpConstraint {
+    name: String,
+    terms: Vec<(String, f64)>,
+    op: Op,
+    rhs: f64,
+}
+
+/// A plain-data mirror of the constraint system, independent of the
+/// solver backend, used to emit the `.lp`/`.mps` files.
+#[derive(Debug, Default)]
+struct LpProblem {
+    variables: Vec<String>,
+    constraints: Vec<LpConstraint>,
+}
+
+impl LpProblem {
+    fn constraint(&mut self, name: impl Into<String>, terms: Vec<(String, f64)>, op: Op, rhs: f64) {
+        self.constraints.push(LpConstraint {
+            name: name.into(),
+            terms,
+            op,
+            rhs,
+        });
+    }
+}
+
+/// Write the constraint system to `path` in the requested format.
+fn emit(lp: &LpProblem, path: &Path, format: OutputFormat) -> Result<()> {
+    let text = match format {
+        OutputFormat::Lp => write_lp(lp),
+        OutputFormat::Mps => write_mps(lp),
     };
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Render the constraint system as CPLEX-style LP text.
+fn write_lp(lp: &LpProblem) -> String {
+    let mut out = String::new();
+    writeln!(out, "Minimize").unwrap();
+    writeln!(out, " obj: 0").unwrap();
+    writeln!(out, "Subject To").unwrap();
+    for c in &lp.constraints {
+        let op = match c.op {
+            Op::Eq => "=",
+            Op::Geq => ">=",
+            Op::Leq => "<=",
+        };
+        let terms: Vec<String> = c.terms.iter().map(|(v, coeff)| format!("{coeff:+} {v}")).collect();
+        writeln!(out, " {}: {} {} {}", c.name, terms.join(" "), op, c.rhs).unwrap();
+    }
+    writeln!(out, "Binaries").unwrap();
+    for v in &lp.variables {
+        writeln!(out, " {v}").unwrap();
+    }
+    writeln!(out, "End").unwrap();
+    out
+}
+
+/// Render the constraint system as fixed-format MPS text. Names longer than
+/// 8 characters overrun the fixed columns; a space still separates each field.
+fn write_mps(lp: &LpProblem) -> String {
+    let mut out = String::new();
+    writeln!(out, "NAME          SUDOKU").unwrap();
+    writeln!(out, "ROWS").unwrap();
+    writeln!(out, " N  obj").unwrap();
+    for c in &lp.constraints {
+        let row_type = match c.op {
+            Op::Eq => "E",
+            Op::Geq => "G",
+            Op::Leq => "L",
+        };
+        writeln!(out, " {row_type}  {}", c.name).unwrap();
+    }
+    writeln!(out, "COLUMNS").unwrap();
+    for v in &lp.variables {
+        for c in &lp.constraints {
+            if let Some((_, coeff)) = c.terms.iter().find(|(name, _)| name == v) {
+                writeln!(out, "    {v:<9} {:<9} {coeff}", c.name).unwrap();
+            }
+        }
+    }
+    writeln!(out, "RHS").unwrap();
+    for c in &lp.constraints {
+        writeln!(out, "    RHS       {:<9} {}", c.name, c.rhs).unwrap();
+    }
+    writeln!(out, "BOUNDS").unwrap();
+    for v in &lp.variables {
+        writeln!(out, " BV BND       {v}").unwrap();
+    }
+    writeln!(out, "ENDATA").unwrap();
+    out
+}
+
+/// Create LP from normalized puzzle and attach it to the given solver backend.
+/// The puzzle length must be a perfect square (e.g. 16, 36, 81, 256, 625),
+/// giving the board's `size`; the box dimensions are inferred from `size`.
+fn generate<S: Solver>(
+    puzzle: &str,
+    solver: S,
+    variants: &Variants,
+) -> Result<(S::Model, LpProblem, Board)> {
+    let size = (puzzle.len() as f64).sqrt().round() as usize;
+    if size == 0 || size * size != puzzle.len() {
+        bail!(
+            "Expected a perfect-square puzzle length (e.g. 81 for 9x9, 256 for 16x16), got {}",
+            puzzle.len()
+        );
+    }
+    let (box_rows, box_cols) = box_dims(size);
 
     // create all the variables and store
     // them in a HashMap for later reference
     type Idx = (usize, usize, usize);
     let mut problem = ProblemVariables::new();
     let mut x: HashMap<Idx, Variable> = HashMap::new();
+    let mut lp = LpProblem::default();
     for row in 1..=size {
         for col in 1..=size {
             for num in 1..=size {
                 let idx = (row, col, num);
                 let name = format!("x_{row}_{col}_{num}");
+                lp.variables.push(name.clone());
                 x.insert(idx, problem.add(variable().binary().name(name)));
             }
         }
     }
 
-    let gurobi =
-        GurobiSolver::new().command_name("/opt/gurobi1001/linux64/bin/gurobi_cl".to_string());
-    let solver = LpSolver(gurobi);
     let mut model = problem.minimise(x.get(&(1, 1, 1)).unwrap()).using(solver);
 
     // Each cell x[r,c] contains one value
     for row in 1..=size {
         for col in 1..=size {
             let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+            let mut terms = Vec::with_capacity(size);
             for num in 1..=size {
                 eqn += x.get(&(row, col, num)).unwrap();
+                terms.push((format!("x_{row}_{col}_{num}"), 1.0));
             }
             model.add_constraint(eqn.eq(1));
+            lp.constraint(format!("cell_{row}_{col}"), terms, Op::Eq, 1.0);
         }
     }
 
     // A value only appears once in each row
-    for row in 1..size {
-        for num in 1..size {
+    for row in 1..=size {
+        for num in 1..=size {
             let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+            let mut terms = Vec::with_capacity(size);
             for col in 1..=size {
                 eqn += x.get(&(row, col, num)).unwrap();
+                terms.push((format!("x_{row}_{col}_{num}"), 1.0));
             }
             model.add_constraint(eqn.eq(1));
+            lp.constraint(format!("row_{row}_{num}"), terms, Op::Eq, 1.0);
         }
     }
 
@@ -99,10 +916,13 @@ fn generate(puzzle: &str) -> Result<Model<GurobiSolver>> {
     for col in 1..=size {
         for num in 1..=size {
             let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+            let mut terms = Vec::with_capacity(size);
             for row in 1..=size {
                 eqn += x.get(&(row, col, num)).unwrap();
+                terms.push((format!("x_{row}_{col}_{num}"), 1.0));
             }
             model.add_constraint(eqn.eq(1));
+            lp.constraint(format!("col_{col}_{num}"), terms, Op::Eq, 1.0);
         }
     }
 
@@ -112,29 +932,334 @@ fn generate(puzzle: &str) -> Result<Model<GurobiSolver>> {
         let start_col = subgrid % (size / box_cols) * box_cols;
         for num in 1..=size {
             let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+            let mut terms = Vec::with_capacity(size);
             for r in 0..box_rows {
                 let row = r + start_row + 1;
                 for c in 0..box_cols {
                     let col = c + start_col + 1;
                     eqn += x.get(&(row, col, num)).unwrap();
+                    terms.push((format!("x_{row}_{col}_{num}"), 1.0));
                 }
             }
             model.add_constraint(eqn.eq(1));
+            lp.constraint(format!("box_{subgrid}_{num}"), terms, Op::Eq, 1.0);
+        }
+    }
+
+    // X-Sudoku: each value appears once on both main diagonals
+    if variants.diagonal {
+        for num in 1..=size {
+            let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+            let mut terms = Vec::with_capacity(size);
+            for i in 1..=size {
+                eqn += x.get(&(i, i, num)).unwrap();
+                terms.push((format!("x_{i}_{i}_{num}"), 1.0));
+            }
+            model.add_constraint(eqn.eq(1));
+            lp.constraint(format!("diag_main_{num}"), terms, Op::Eq, 1.0);
+
+            let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+            let mut terms = Vec::with_capacity(size);
+            for i in 1..=size {
+                let col = size + 1 - i;
+                eqn += x.get(&(i, col, num)).unwrap();
+                terms.push((format!("x_{i}_{col}_{num}"), 1.0));
+            }
+            model.add_constraint(eqn.eq(1));
+            lp.constraint(format!("diag_anti_{num}"), terms, Op::Eq, 1.0);
         }
     }
 
+    // Windoku: each value also appears once in the 4 shaded interior 3x3 boxes
+    if variants.windoku {
+        if box_rows != 3 || box_cols != 3 {
+            bail!("--windoku is only supported for 9x9 puzzles");
+        }
+        for &start_row in &[2usize, 6] {
+            for &start_col in &[2usize, 6] {
+                for num in 1..=size {
+                    let mut eqn: good_lp::Expression = Expression::with_capacity(size);
+                    let mut terms = Vec::with_capacity(size);
+                    for r in 0..box_rows {
+                        let row = start_row + r;
+                        for c in 0..box_cols {
+                            let col = start_col + c;
+                            eqn += x.get(&(row, col, num)).unwrap();
+                            terms.push((format!("x_{row}_{col}_{num}"), 1.0));
+                        }
+                    }
+                    model.add_constraint(eqn.eq(1));
+                    lp.constraint(
+                        format!("window_{start_row}_{start_col}_{num}"),
+                        terms,
+                        Op::Eq,
+                        1.0,
+                    );
+                }
+            }
+        }
+    }
+
+    // Killer Sudoku: each cage's cells must sum to its target
+    for (i, cage) in variants.cages.iter().enumerate() {
+        let mut eqn: good_lp::Expression = Expression::with_capacity(cage.cells.len() * size);
+        let mut terms = Vec::with_capacity(cage.cells.len() * size);
+        for &(row, col) in &cage.cells {
+            for num in 1..=size {
+                eqn += num as f64 * *x.get(&(row, col, num)).unwrap();
+                terms.push((format!("x_{row}_{col}_{num}"), num as f64));
+            }
+        }
+        model.add_constraint(eqn.eq(cage.target as f64));
+        lp.constraint(format!("cage_{i}"), terms, Op::Eq, cage.target as f64);
+    }
+
+    // No-good cuts forbidding previously found solutions (see --check-unique)
+    for (i, assignment) in variants.exclude.iter().enumerate() {
+        let mut eqn: good_lp::Expression = Expression::with_capacity(assignment.len());
+        let mut terms = Vec::with_capacity(assignment.len());
+        for &(row, col, num) in assignment {
+            eqn += x.get(&(row, col, num)).unwrap();
+            terms.push((format!("x_{row}_{col}_{num}"), 1.0));
+        }
+        let rhs = assignment.len() as f64 - 1.0;
+        model.add_constraint(eqn.leq(rhs));
+        lp.constraint(format!("nogood_{i}"), terms, Op::Leq, rhs);
+    }
+
     // The original clues from the puzzle
     let mut eqn: good_lp::Expression = Expression::with_capacity(size * size);
+    let mut terms = Vec::new();
     let mut count = 0;
     for (i, c) in puzzle.chars().enumerate().filter(|(_, c)| *c != '0') {
-        // We know this unwrap can't fail
-        let val = c.to_digit(10).unwrap() as usize;
+        let val = token_value(c);
         let row = i / size + 1;
         let col = i % size + 1;
         eqn += x.get(&(row, col, val)).unwrap();
+        terms.push((format!("x_{row}_{col}_{val}"), 1.0));
         count += 1;
     }
     model.add_constraint(eqn.geq(count));
+    lp.constraint("clues", terms, Op::Geq, count as f64);
+
+    let board = Board {
+        size,
+        box_rows,
+        box_cols,
+        x,
+    };
+    Ok((model, lp, board))
+}
+
+/// A Hidato puzzle: a (possibly non-rectangular, holed) grid of playable
+/// cells, some pre-filled with their final value.
+struct HidatoPuzzle {
+    rows: usize,
+    cols: usize,
+    /// `None` for a hole (not part of the puzzle); `Some(0)` for a blank
+    /// playable cell; `Some(v)` for a cell pre-filled with value `v`.
+    cells: Vec<Vec<Option<usize>>>,
+}
+
+/// Load a Hidato puzzle from a text grid: whitespace-separated tokens, one
+/// row per line, where `.` is a blank playable cell, `#` is a hole, and a
+/// number is a cell pre-filled with that value.
+fn load_hidato(file: &Path) -> Result<HidatoPuzzle> {
+    let text = std::fs::read_to_string(file)?;
+    let mut cells = Vec::new();
+    let mut cols = None;
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        let row: Vec<Option<usize>> = tokens
+            .iter()
+            .map(|&tok| match tok {
+                "." => Ok(Some(0)),
+                "#" => Ok(None),
+                n => n.parse().map(Some).context("invalid Hidato cell token"),
+            })
+            .collect::<Result<_>>()?;
+        match cols {
+            None => cols = Some(row.len()),
+            Some(c) if c != row.len() => bail!("Hidato puzzle rows must all have the same width"),
+            Some(_) => {}
+        }
+        cells.push(row);
+    }
+    let cols = cols.context("Hidato puzzle file is empty")?;
+    Ok(HidatoPuzzle {
+        rows: cells.len(),
+        cols,
+        cells,
+    })
+}
+
+/// The `y[cell,value]` variables for a Hidato puzzle, kept around after
+/// `generate_hidato` so a found solution can be read back into a grid.
+struct HidatoBoard {
+    rows: usize,
+    cols: usize,
+    /// The number of playable cells, and so the highest value in the puzzle.
+    k: usize,
+    /// Playable cells in the order they were assigned a cell index: `cell`
+    /// indexes into this, giving back its `(row, col)`.
+    playable: Vec<(usize, usize)>,
+    y: HashMap<(usize, usize), Variable>,
+}
+
+impl HidatoBoard {
+    /// Read back the solved grid as `rows`x`cols`, `None` for holes.
+    fn read_grid<Sol: Solution>(&self, solution: &Sol) -> Vec<Vec<Option<usize>>> {
+        let mut grid = vec![vec![None; self.cols]; self.rows];
+        for (cell, &(r, c)) in self.playable.iter().enumerate() {
+            let value = (1..=self.k)
+                .find(|&v| solution.value(self.y[&(cell, v)]) > 0.5)
+                .expect("every cell is assigned exactly one value");
+            grid[r][c] = Some(value);
+        }
+        grid
+    }
+
+    /// The `(cell, value)` assignment implied by a solved grid, suitable for
+    /// forbidding via a no-good cut.
+    fn assignment(&self, grid: &[Vec<Option<usize>>]) -> Vec<(usize, usize)> {
+        self.playable
+            .iter()
+            .enumerate()
+            .map(|(cell, &(r, c))| (cell, grid[r][c].expect("playable cell has a value")))
+            .collect()
+    }
+}
+
+/// Create the Hidato LP from a loaded puzzle and attach it to the given
+/// solver backend. Binary `y[cell,v]` means "cell holds value v"; every cell
+/// holds exactly one value, every value occupies exactly one cell, pre-filled
+/// cells are fixed, and consecutive values are linked through Moore
+/// (8-neighbor) adjacency: `y[c,v] <= sum_{c' in Moore(c)} y[c',v+1]`.
+fn generate_hidato<S: Solver>(
+    puzzle: &HidatoPuzzle,
+    solver: S,
+    exclude: &[Vec<(usize, usize)>],
+) -> Result<(S::Model, LpProblem, HidatoBoard)> {
+    let playable: Vec<(usize, usize)> = (0..puzzle.rows)
+        .flat_map(|r| (0..puzzle.cols).map(move |c| (r, c)))
+        .filter(|&(r, c)| puzzle.cells[r][c].is_some())
+        .collect();
+    let k = playable.len();
+    if k == 0 {
+        bail!("Hidato puzzle has no playable cells");
+    }
+    for &(r, c) in &playable {
+        if let Some(v) = puzzle.cells[r][c].filter(|&v| v != 0) {
+            if v > k {
+                bail!("Hidato clue {v} at cell ({r}, {c}) exceeds the {k} playable cells");
+            }
+        }
+    }
+
+    // create all the variables and store
+    // them in a HashMap for later reference
+    type Idx = (usize, usize);
+    let mut problem = ProblemVariables::new();
+    let mut y: HashMap<Idx, Variable> = HashMap::new();
+    let mut lp = LpProblem::default();
+    for cell in 0..k {
+        for v in 1..=k {
+            let idx = (cell, v);
+            let name = format!("y_{cell}_{v}");
+            lp.variables.push(name.clone());
+            y.insert(idx, problem.add(variable().binary().name(name)));
+        }
+    }
+
+    let mut model = problem.minimise(y.get(&(0, 1)).unwrap()).using(solver);
+
+    // Each cell holds exactly one value
+    for cell in 0..k {
+        let mut eqn: good_lp::Expression = Expression::with_capacity(k);
+        let mut terms = Vec::with_capacity(k);
+        for v in 1..=k {
+            eqn += y.get(&(cell, v)).unwrap();
+            terms.push((format!("y_{cell}_{v}"), 1.0));
+        }
+        model.add_constraint(eqn.eq(1));
+        lp.constraint(format!("cell_{cell}"), terms, Op::Eq, 1.0);
+    }
 
-    Ok(model)
+    // Each value occupies exactly one cell
+    for v in 1..=k {
+        let mut eqn: good_lp::Expression = Expression::with_capacity(k);
+        let mut terms = Vec::with_capacity(k);
+        for cell in 0..k {
+            eqn += y.get(&(cell, v)).unwrap();
+            terms.push((format!("y_{cell}_{v}"), 1.0));
+        }
+        model.add_constraint(eqn.eq(1));
+        lp.constraint(format!("value_{v}"), terms, Op::Eq, 1.0);
+    }
+
+    // Pre-filled cells are fixed to their given value
+    for (cell, &(r, c)) in playable.iter().enumerate() {
+        if let Some(v) = puzzle.cells[r][c].filter(|&v| v != 0) {
+            let mut eqn: good_lp::Expression = Expression::with_capacity(1);
+            eqn += y.get(&(cell, v)).unwrap();
+            model.add_constraint(eqn.eq(1));
+            lp.constraint(
+                format!("fixed_{cell}"),
+                vec![(format!("y_{cell}_{v}"), 1.0)],
+                Op::Eq,
+                1.0,
+            );
+        }
+    }
+
+    // Consecutive values must sit in Moore-adjacent cells
+    for (cell, &(r, c)) in playable.iter().enumerate() {
+        let neighbors: Vec<usize> = playable
+            .iter()
+            .enumerate()
+            .filter(|&(other, &(nr, nc))| {
+                other != cell
+                    && (nr as isize - r as isize).abs() <= 1
+                    && (nc as isize - c as isize).abs() <= 1
+            })
+            .map(|(other, _)| other)
+            .collect();
+        for v in 1..k {
+            let mut eqn: good_lp::Expression = Expression::with_capacity(neighbors.len() + 1);
+            let mut terms = Vec::with_capacity(neighbors.len() + 1);
+            eqn += y.get(&(cell, v)).unwrap();
+            terms.push((format!("y_{cell}_{v}"), 1.0));
+            for &other in &neighbors {
+                eqn -= y.get(&(other, v + 1)).unwrap();
+                terms.push((format!("y_{other}_{}", v + 1), -1.0));
+            }
+            model.add_constraint(eqn.leq(0));
+            lp.constraint(format!("link_{cell}_{v}"), terms, Op::Leq, 0.0);
+        }
+    }
+
+    // No-good cuts forbidding previously found solutions (see --check-unique)
+    for (i, assignment) in exclude.iter().enumerate() {
+        let mut eqn: good_lp::Expression = Expression::with_capacity(assignment.len());
+        let mut terms = Vec::with_capacity(assignment.len());
+        for &(cell, v) in assignment {
+            eqn += y.get(&(cell, v)).unwrap();
+            terms.push((format!("y_{cell}_{v}"), 1.0));
+        }
+        let rhs = assignment.len() as f64 - 1.0;
+        model.add_constraint(eqn.leq(rhs));
+        lp.constraint(format!("nogood_{i}"), terms, Op::Leq, rhs);
+    }
+
+    let board = HidatoBoard {
+        rows: puzzle.rows,
+        cols: puzzle.cols,
+        k,
+        playable,
+        y,
+    };
+    Ok((model, lp, board))
 }